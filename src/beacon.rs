@@ -0,0 +1,234 @@
+// Rendezvous-based peer discovery as an alternative to LAN UDP broadcast.
+//
+// `announce_presence` only reaches the local subnet, so neurons separated by a
+// router or NAT never see each other. Modeled on VpnCloud's `BeaconSerializer`,
+// a beacon publishes a neuron's `SocketAddr` and public key into a shared
+// location that every neuron can read: either a local file path or a plain
+// HTTP endpoint (`https://` locations parse, but every request to them fails
+// because this build has no TLS support). Neurons refresh their own entry on the announce timer and seed
+// `peers` from the entries the others have published.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+
+/// How a neuron finds its peers.
+#[derive(Debug, Clone)]
+pub enum Discovery {
+    /// The original LAN UDP broadcast on `DISCOVERY_PORT`.
+    Broadcast,
+    /// Publish to and read from a shared rendezvous location.
+    Beacon(Backend),
+}
+
+/// Where beacon entries are stored.
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// A shared file holding one `"<addr> <pubkey-hex>"` line per neuron.
+    File(PathBuf),
+    /// An HTTP endpoint that serves the beacon on `GET` and accepts the
+    /// neuron's own entry on `PUT`. An `https://` URL is stored here too, but
+    /// publishing to or fetching from it returns an `Unsupported` error.
+    Http(String),
+}
+
+impl Discovery {
+    /// Parse a `--discovery` argument: `broadcast` or `beacon:<path-or-url>`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if spec == "broadcast" {
+            return Ok(Discovery::Broadcast);
+        }
+        if let Some(location) = spec.strip_prefix("beacon:") {
+            if location.is_empty() {
+                return Err("beacon discovery requires a path or URL".to_string());
+            }
+            let backend = if location.starts_with("http://") || location.starts_with("https://") {
+                Backend::Http(location.to_string())
+            } else {
+                Backend::File(PathBuf::from(location))
+            };
+            return Ok(Discovery::Beacon(backend));
+        }
+        Err(format!("unknown discovery backend: {}", spec))
+    }
+}
+
+impl Backend {
+    /// Publish `entry` (a `"<addr> <pubkey-hex>"` line), replacing any previous
+    /// entry this neuron wrote for the same address.
+    pub fn publish(&self, entry: &str) -> io::Result<()> {
+        match self {
+            Backend::File(path) => {
+                let address = entry.split_whitespace().next().unwrap_or("");
+                // Serialize the whole read-modify-write behind an advisory lock so
+                // concurrent publishers (every neuron runs as a thread in one
+                // process) don't each read the file, drop only their own line, and
+                // clobber each other's concurrent additions.
+                let _lock = FileLock::acquire(path)?;
+                let mut lines: Vec<String> = read_lines(path)?
+                    .into_iter()
+                    .filter(|line| line.split_whitespace().next() != Some(address))
+                    .collect();
+                lines.push(entry.to_string());
+                // Write to a per-neuron temp file and rename so readers never see a
+                // half-written beacon and concurrent writers don't share one temp.
+                let tmp = path.with_extension(format!("{}.tmp", sanitize(address)));
+                fs::write(&tmp, lines.join("\n"))?;
+                fs::rename(&tmp, path)
+            }
+            Backend::Http(url) => http_request(url, "PUT", Some(entry)).map(|_| ()),
+        }
+    }
+
+    /// Read every published beacon entry.
+    pub fn fetch(&self) -> io::Result<Vec<String>> {
+        match self {
+            Backend::File(path) => read_lines(path),
+            Backend::Http(url) => {
+                let body = http_request(url, "GET", None)?;
+                Ok(body.lines().map(|line| line.to_string()).collect())
+            }
+        }
+    }
+}
+
+/// A crude advisory lock built on an exclusively-created sibling `.lock` file.
+/// Held for the duration of a beacon read-modify-write so concurrent publishers
+/// serialize instead of losing each other's entries. Released on drop.
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    fn acquire(target: &PathBuf) -> io::Result<Self> {
+        let path = target.with_extension("lock");
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(FileLock { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Replace path-unfriendly characters in an address so it can form a unique
+/// per-neuron temp-file suffix.
+fn sanitize(label: &str) -> String {
+    label.replace([':', '.', '/'], "_")
+}
+
+/// Read a beacon file into its non-empty lines, treating a missing file as an
+/// empty beacon rather than an error.
+fn read_lines(path: &PathBuf) -> io::Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Minimal HTTP/1.0 client for the beacon backend. Only plain `http://` is
+/// supported here; `https://` needs a TLS-enabled build and is rejected.
+fn http_request(url: &str, method: &str, body: Option<&str>) -> io::Result<String> {
+    if url.starts_with("https://") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "https beacon requires a TLS-enabled build",
+        ));
+    }
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "beacon URL must start with http://")
+    })?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let host_port = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let mut stream = TcpStream::connect(&host_port)?;
+    let payload = body.unwrap_or("");
+    let request = format!(
+        "{method} {path} HTTP/1.0\r\nHost: {authority}\r\nContent-Length: {len}\r\nConnection: close\r\n\r\n{payload}",
+        len = payload.len(),
+    );
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    // Strip the status line and headers; the body follows the blank line.
+    match response.split_once("\r\n\r\n") {
+        Some((_, body)) => Ok(body.to_string()),
+        None => Ok(String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempBeacon {
+        path: PathBuf,
+    }
+
+    impl TempBeacon {
+        fn new(tag: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("neuron_beacon_{}_{}.txt", std::process::id(), tag));
+            let _ = fs::remove_file(&path);
+            TempBeacon { path }
+        }
+
+        fn backend(&self) -> Backend {
+            Backend::File(self.path.clone())
+        }
+    }
+
+    impl Drop for TempBeacon {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn file_publish_keeps_other_neurons_entries() {
+        let beacon = TempBeacon::new("merge");
+        let backend = beacon.backend();
+        backend.publish("127.0.0.1:5003 aa").unwrap();
+        backend.publish("127.0.0.1:5004 bb").unwrap();
+
+        let mut entries = backend.fetch().unwrap();
+        entries.sort();
+        assert_eq!(entries, vec!["127.0.0.1:5003 aa", "127.0.0.1:5004 bb"]);
+    }
+
+    #[test]
+    fn file_publish_replaces_own_entry() {
+        let beacon = TempBeacon::new("replace");
+        let backend = beacon.backend();
+        backend.publish("127.0.0.1:5003 aa").unwrap();
+        backend.publish("127.0.0.1:5003 cc").unwrap();
+
+        let entries = backend.fetch().unwrap();
+        assert_eq!(entries, vec!["127.0.0.1:5003 cc"]);
+    }
+}
@@ -1,31 +1,164 @@
+mod beacon;
+mod crypto;
 mod ncp;
+mod poll;
+mod traffic;
 
 use std::{
+    collections::HashMap,
     env,
-    io::{ErrorKind, Read},
-    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
+    io::Read,
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, UdpSocket},
+    os::unix::io::{AsRawFd, RawFd},
     sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use ncp::{connect, listen, Message, SignalType};
+use rand_core::{OsRng, RngCore};
+
+use poll::{Poll, WaitResult};
+
+use beacon::Discovery;
+use crypto::{Identity, NodeId};
+use ncp::{connect, connect_timeout, listen, Decoded, Message, SignalType};
+use traffic::TrafficStats;
 
 const DISCOVERY_PORT: u16 = 5002;
 const NEURON_PORT_START: u16 = 5003;
-const DISCOVERY_INTERVAL_MS: u64 = 1000;
 const ANNOUNCE_INTERVAL_MS: u64 = 2000;
+const ELECTION_TIMEOUT_MS: u64 = 1500; // How long to wait for a higher peer to answer
+const ELECTION_INTERVAL_MS: u64 = 3000; // How often to (re-)start an election while leaderless
+const KEEPALIVE_INTERVAL_MS: u64 = 5000; // How often to remind peers we are alive
+const HOUSEKEEPING_INTERVAL_MS: u64 = 5000; // How often to sweep for dead peers
+const STATS_INTERVAL_MS: u64 = 10000; // How often to report per-peer traffic
+const GOSSIP_INTERVAL_MS: u64 = 3000; // How often to exchange peer lists
+const DEFAULT_FANOUT: usize = 3; // Peers contacted per gossip round
+const DEFAULT_PEER_TIMEOUT: Duration = Duration::from_secs(30); // Forget peers silent this long
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5); // Give up on a dial that doesn't connect
+
+/// Bookkeeping for a single known peer.
+#[derive(Debug)]
+struct PeerEntry {
+    id: u16,
+    last_seen: Instant,
+    advertised_key: Option<NodeId>, // Public key learned from discovery, if any
+}
+
+/// Self-healing view of the mesh: peers that stop sending keepalives (or any
+/// other message) eventually age out, so `dial_lower_peers` no longer chases
+/// addresses that have left.
+#[derive(Debug, Default)]
+struct PeerList {
+    entries: HashMap<SocketAddr, PeerEntry>,
+}
+
+impl PeerList {
+    fn new() -> Self {
+        PeerList { entries: HashMap::new() }
+    }
+
+    fn contains(&self, address: &SocketAddr) -> bool {
+        self.entries.contains_key(address)
+    }
+
+    /// Insert the peer if unknown, otherwise bump its `last_seen` timestamp.
+    fn seen(&mut self, address: SocketAddr) {
+        self.seen_with_key(address, None);
+    }
+
+    /// Like [`seen`], but also records (or refreshes) the peer's advertised
+    /// public key when one is supplied.
+    fn seen_with_key(&mut self, address: SocketAddr, key: Option<NodeId>) {
+        let id = id_from_addr(&address);
+        self.entries
+            .entry(address)
+            .and_modify(|entry| {
+                entry.last_seen = Instant::now();
+                if key.is_some() {
+                    entry.advertised_key = key;
+                }
+            })
+            .or_insert_with(|| PeerEntry { id, last_seen: Instant::now(), advertised_key: key });
+    }
+
+    fn addresses(&self) -> Vec<SocketAddr> {
+        self.entries.keys().copied().collect()
+    }
+
+    /// The public key advertised for `address`, if one has been learned.
+    fn advertised_key(&self, address: &SocketAddr) -> Option<NodeId> {
+        self.entries.get(address).and_then(|entry| entry.advertised_key)
+    }
+
+    /// Pick a random subset of at most `count` known peer addresses, with no
+    /// duplicates. `gossip_peers` applies the same sampling to its live
+    /// connections rather than calling this method.
+    fn subset(&self, count: usize) -> Vec<SocketAddr> {
+        let mut addresses = self.addresses();
+        if addresses.len() <= count {
+            return addresses;
+        }
+        // Partial Fisher-Yates: move `count` randomly chosen entries to the
+        // front, then keep that prefix.
+        let mut rng = OsRng;
+        for i in 0..count {
+            let j = i + (rng.next_u32() as usize) % (addresses.len() - i);
+            addresses.swap(i, j);
+        }
+        addresses.truncate(count);
+        addresses
+    }
+
+    /// Drop and return every peer that has been silent for longer than `timeout`.
+    fn timeout(&mut self, timeout: Duration) -> Vec<SocketAddr> {
+        let now = Instant::now();
+        let stale: Vec<SocketAddr> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now.duration_since(entry.last_seen) > timeout)
+            .map(|(address, _)| *address)
+            .collect();
+        for address in &stale {
+            self.entries.remove(address);
+        }
+        stale
+    }
+}
 
 #[derive(Debug)]
 struct Neuron {
     id: u16,
     address: SocketAddr,
-    peers: Arc<Mutex<Vec<SocketAddr>>>,
+    peers: Arc<Mutex<PeerList>>,
+    leader: Arc<Mutex<Option<u16>>>, // Id of the neuron currently believed to be coordinator
+    identity: Arc<Identity>, // Ed25519 keypair proving this neuron's identity
+    traffic: Arc<Mutex<TrafficStats>>, // Per-peer / per-signal byte and frame counters
+    statsd: Option<SocketAddr>, // Optional statsd endpoint for counter export
+    discovery: Discovery, // How this neuron finds its peers (broadcast or beacon)
+    fanout: usize, // Peers contacted per gossip round
     discovery_send_socket: std::net::UdpSocket, // Add a socket for sending discovery
 }
 
+/// Serialize a batch of peer addresses as newline-separated text for a
+/// `SignalType::PeerExchange` payload.
+fn encode_peer_batch(addresses: &[SocketAddr]) -> Vec<u8> {
+    addresses
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .into_bytes()
+}
+
+/// Recover a neuron id from its well-known address. Ports are allocated as
+/// `NEURON_PORT_START + id`, so the id is simply the offset from the base port.
+fn id_from_addr(address: &SocketAddr) -> u16 {
+    address.port().saturating_sub(NEURON_PORT_START)
+}
+
 impl Neuron {
-    fn new(id: u16) -> Self {
+    fn new(id: u16, statsd: Option<SocketAddr>, discovery: Discovery, fanout: usize) -> Self {
         let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), NEURON_PORT_START + id);
         let bind_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0); // Bind to any available port
         let discovery_send_socket =
@@ -35,231 +168,699 @@ impl Neuron {
         Neuron {
             id,
             address,
-            peers: Arc::new(Mutex::new(Vec::new())),
+            peers: Arc::new(Mutex::new(PeerList::new())),
+            leader: Arc::new(Mutex::new(None)),
+            identity: Arc::new(Identity::generate()),
+            traffic: Arc::new(Mutex::new(TrafficStats::new())),
+            statsd,
+            discovery,
+            fanout,
             discovery_send_socket,
         }
     }
 
-    fn announce_presence(self: Arc<Self>) -> Result<(), std::io::Error> {
-        let discovery_address = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), DISCOVERY_PORT);
-        let announcement = self.address.to_string().as_bytes().to_vec();
-        self.discovery_send_socket.send_to(&announcement, discovery_address)?;
-        println!("Neuron {} ({}) announced its presence. | peers={:?}", self.id, self.address, self.peers);
-        Ok(())
-    }
-
-    fn listen_for_announcements(self: Arc<Self>) {
-        let discovery_listen_address =
-            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), DISCOVERY_PORT + self.id); // Use a unique port
-
-        let discovery_socket =
-            std::net::UdpSocket::bind(discovery_listen_address)
-                .expect("Failed to bind to discovery port");
-
+    /// Run one round of the Bully algorithm. The initiator contacts every peer
+    /// with a higher id; if any of them answers with a `Nomination` before the
+    /// timeout, the initiator steps down and waits to hear a `Victory`.
+    /// Otherwise it declares itself coordinator and announces a `Victory` to the
+    /// lower-id peers.
+    fn start_election(self: Arc<Self>) {
         let peers = Arc::clone(&self.peers);
+        let leader = Arc::clone(&self.leader);
+        let identity = Arc::clone(&self.identity);
+        let traffic = Arc::clone(&self.traffic);
         let self_address = self.address;
         let self_id = self.id;
 
         thread::spawn(move || {
-            let mut buffer = [0; 1024];
-            loop {
-                match discovery_socket.recv_from(&mut buffer) {
-                    Ok((size, src_address)) => {
-                        if src_address != self_address {
-                            if let Ok(peer_address_str) = String::from_utf8(buffer[..size].to_vec()) {
-                                if let Ok(peer_address) = peer_address_str.parse::<SocketAddr>() {
-                                    let mut peers_guard = peers.lock().unwrap();
-                                    if !peers_guard.contains(&peer_address) {
-                                        println!(
-                                            "Neuron {} ({}) discovered peer: {}",
-                                            self_id, self_address, peer_address
-                                        );
-                                        peers_guard.push(peer_address);
-
-                                    }
-                                } else {
-                                    eprintln!(
-                                        "Neuron {} ({}) received invalid address: {}",
-                                        self_id, self_address, peer_address_str
-                                    );
-                                }
-                            } else {
-                                eprintln!(
-                                    "Neuron {} ({}) received non-UTF8 data from: {}",
-                                    self_id, self_address, src_address
-                                );
-                            }
+            let higher_peers: Vec<SocketAddr> = {
+                let peers_guard = peers.lock().unwrap();
+                peers_guard
+                    .addresses()
+                    .into_iter()
+                    .filter(|peer| *peer > self_address)
+                    .collect()
+            };
+
+            println!(
+                "Neuron {} ({}) starting election with {} higher peer(s).",
+                self_id, self_address, higher_peers.len()
+            );
+
+            let mut nominated = false;
+            for peer_address in &higher_peers {
+                match connect(&peer_address.to_string()) {
+                    Ok(mut stream) => {
+                        let expected = peers.lock().unwrap().advertised_key(peer_address);
+                        if crypto::handshake(&mut stream, &identity, expected, self_address).is_err() {
+                            continue;
                         }
-                    }
-                    Err(e) => {
-                        if e.kind() != ErrorKind::WouldBlock {
+                        let request = Message::from_values(self_id, SignalType::ElectionRequest, &[self_id as f32]);
+                        if let Err(e) = request.send(&mut stream) {
                             eprintln!(
-                                "Neuron {} ({}) error receiving discovery: {}",
-                                self_id, self_address, e
+                                "Neuron {} ({}) error sending ElectionRequest to {}: {}",
+                                self_id, self_address, peer_address, e
+                            );
+                            continue;
+                        }
+                        traffic.lock().unwrap().count_out(
+                            *peer_address,
+                            request.signal_type.to_u8(),
+                            request.frame_len(),
+                        );
+                        stream
+                            .set_read_timeout(Some(Duration::from_millis(ELECTION_TIMEOUT_MS)))
+                            .ok();
+                        if let Ok(Some(message)) = Message::receive(&mut stream) {
+                            traffic.lock().unwrap().count_in(
+                                *peer_address,
+                                message.signal_type.to_u8(),
+                                message.frame_len(),
                             );
+                            if let SignalType::Nomination = message.signal_type {
+                                nominated = true;
+                            }
                         }
                     }
+                    Err(e) => {
+                        eprintln!(
+                            "Neuron {} ({}) error contacting higher peer {}: {}",
+                            self_id, self_address, peer_address, e
+                        );
+                    }
                 }
+            }
 
-                thread::sleep(Duration::from_millis(DISCOVERY_INTERVAL_MS));
+            if nominated {
+                // A higher neuron is alive; step down and wait for its Victory.
+                println!(
+                    "Neuron {} ({}) stepped down, waiting for a coordinator.",
+                    self_id, self_address
+                );
+                return;
             }
-        });
-    }
 
-    fn connect_to_peers(self: Arc<Self>) {
-        let peers = Arc::clone(&self.peers);
-        let self_address = self.address;
-        let self_id = self.id;
+            // Nobody higher answered: win the election and tell the lower peers.
+            *leader.lock().unwrap() = Some(self_id);
+            println!("Neuron {} ({}) won the election and is now coordinator.", self_id, self_address);
 
-        thread::spawn(move || {
-            loop {
+            let lower_peers: Vec<SocketAddr> = {
                 let peers_guard = peers.lock().unwrap();
-                for peer_address in &*peers_guard {
-                    if *peer_address < self_address {
-                        println!(
-                            "Neuron {} ({}) attempting to connect to peer: {}",
-                            self_id, self_address, peer_address
+                peers_guard
+                    .addresses()
+                    .into_iter()
+                    .filter(|peer| *peer < self_address)
+                    .collect()
+            };
+            for peer_address in &lower_peers {
+                if let Ok(mut stream) = connect(&peer_address.to_string()) {
+                    let expected = peers.lock().unwrap().advertised_key(peer_address);
+                    if crypto::handshake(&mut stream, &identity, expected, self_address).is_err() {
+                        continue;
+                    }
+                    let victory = Message::from_values(self_id, SignalType::Victory, &[self_id as f32]);
+                    if let Err(e) = victory.send(&mut stream) {
+                        eprintln!(
+                            "Neuron {} ({}) error announcing Victory to {}: {}",
+                            self_id, self_address, peer_address, e
+                        );
+                    } else {
+                        traffic.lock().unwrap().count_out(
+                            *peer_address,
+                            victory.signal_type.to_u8(),
+                            victory.frame_len(),
                         );
-                        match connect(&peer_address.to_string()) {
-                            Ok(mut stream) => {
-                                println!(
-                                    "Neuron {} ({}) successfully connected to peer: {}",
-                                    self_id, self_address, peer_address
-                                );
-                                // You can now use 'stream' to send and receive NCP messages
-                                // For example, send an initial handshake or discovery confirmation
-                                if let Err(e) = Message::new(self_id, SignalType::Data, self_id as f32)
-                                    .send(&mut stream)
-                                {
-                                    eprintln!(
-                                        "Neuron {} ({}) error sending initial message to {}: {}",
-                                        self_id, self_address, peer_address, e
-                                    );
-                                }
-                                // Handle communication with this peer in a separate thread or loop
-                                Self::handle_peer_communication(Arc::clone(&self), stream, *peer_address);
-                            }
-                            Err(e) => {
-                                eprintln!(
-                                    "Neuron {} ({}) error connecting to {}: {}",
-                                    self_id, self_address, peer_address, e
-
-                                );
-                            }
-                        }
                     }
                 }
-                drop(peers_guard); // Release the lock
-                thread::sleep(Duration::from_secs(5)); // Attempt connections periodically
             }
         });
+    }
 
+    /// The beacon/announcement line describing this neuron:
+    /// `"<address> <public-key-hex>"`.
+    fn announcement(&self) -> String {
+        format!("{} {}", self.address, crypto::to_hex(&self.identity.node_id()))
     }
 
-    fn handle_peer_communication(self: Arc<Self>, mut stream: TcpStream, peer_address: SocketAddr) {
-        let self_id = self.id;
-        let self_address = self.address;
-        thread::spawn(move || {
-            println!(
-                "Neuron {} ({}) handling communication with peer: {}",
-                self_id, self_address, peer_address
-            );
-            loop {
-                match stream.read(&mut [0; 128]) {
-                    Ok(0) => {
-                        println!(
-                            "Neuron {} ({}) peer {} disconnected.",
+    fn announce_presence(self: Arc<Self>) -> Result<(), std::io::Error> {
+        match &self.discovery {
+            Discovery::Broadcast => {
+                let discovery_address =
+                    SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), DISCOVERY_PORT);
+                // Advertise "<address> <public-key-hex>" so peers can pin our identity.
+                let announcement = self.announcement().into_bytes();
+                self.discovery_send_socket.send_to(&announcement, discovery_address)?;
+            }
+            Discovery::Beacon(backend) => {
+                backend.publish(&self.announcement())?;
+                for line in backend.fetch()? {
+                    self.merge_announcement(&line);
+                }
+            }
+        }
+        println!("Neuron {} ({}) announced its presence. | peers={:?}", self.id, self.address, self.peers);
+        Ok(())
+    }
+
+    /// Parse an announcement line (`"<address> <public-key-hex>"`) and seed the
+    /// peer list from it, ignoring our own entry. Shared by the UDP discovery
+    /// path and the beacon backend.
+    fn merge_announcement(&self, line: &str) {
+        let mut parts = line.split_whitespace();
+        let parsed = parts.next().and_then(|a| a.parse::<SocketAddr>().ok());
+        let advertised_key = parts.next().and_then(crypto::from_hex);
+        if let Some(peer_address) = parsed {
+            if peer_address == self.address {
+                return;
+            }
+            let mut peers_guard = self.peers.lock().unwrap();
+            if !peers_guard.contains(&peer_address) {
+                println!("Neuron {} ({}) discovered peer: {}", self.id, self.address, peer_address);
+            }
+            peers_guard.seen_with_key(peer_address, advertised_key);
+        }
+    }
+
+    /// Single-reactor I/O loop for one neuron.
+    ///
+    /// All sockets — the discovery UDP socket, the TCP listener, and every
+    /// connected peer stream — are registered with one `epoll` instance and set
+    /// non-blocking. The loop blocks in [`Poll::wait`] until a descriptor is
+    /// readable or the nearest timer is due, then dispatches. Announce,
+    /// keepalive, housekeeping, and connect timers are checked against the poll
+    /// deadline rather than run from dedicated sleeping threads.
+    fn run_reactor(self: Arc<Self>) {
+        let mut poller = Poll::new().expect("Failed to create epoll instance");
+
+        let discovery_address =
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), DISCOVERY_PORT + self.id);
+        let discovery = UdpSocket::bind(discovery_address).expect("Failed to bind discovery socket");
+        discovery.set_nonblocking(true).expect("Failed to set discovery non-blocking");
+        let discovery_fd = discovery.as_raw_fd();
+
+        let listener = listen(&self.address.to_string()).expect("Failed to start listener");
+        listener.set_nonblocking(true).expect("Failed to set listener non-blocking");
+        let listener_fd = listener.as_raw_fd();
+
+        poller.register(discovery_fd).expect("Failed to register discovery socket");
+        poller.register(listener_fd).expect("Failed to register listener");
+        println!("Neuron {} ({}) reactor started.", self.id, self.address);
+
+        // fd -> (stream, peer address) for every live peer connection.
+        let mut connections: HashMap<RawFd, (TcpStream, SocketAddr)> = HashMap::new();
+        // fd -> bytes received but not yet forming a complete frame. Buffering
+        // here keeps the reactor non-blocking when a peer sends a frame in
+        // pieces, so one slow sender can't stall every other connection.
+        let mut read_buffers: HashMap<RawFd, Vec<u8>> = HashMap::new();
+        let mut buffer = [0u8; 1024];
+
+        let announce_every = Duration::from_millis(ANNOUNCE_INTERVAL_MS);
+        let keepalive_every = Duration::from_millis(KEEPALIVE_INTERVAL_MS);
+        let housekeep_every = Duration::from_millis(HOUSEKEEPING_INTERVAL_MS);
+        let stats_every = Duration::from_millis(STATS_INTERVAL_MS);
+        let gossip_every = Duration::from_millis(GOSSIP_INTERVAL_MS);
+        let election_every = Duration::from_millis(ELECTION_INTERVAL_MS);
+        let connect_every = Duration::from_secs(5);
+        let mut next_announce = Instant::now();
+        let mut next_keepalive = Instant::now() + keepalive_every;
+        let mut next_housekeep = Instant::now() + housekeep_every;
+        let mut next_stats = Instant::now() + stats_every;
+        let mut next_gossip = Instant::now() + gossip_every;
+        let mut next_election = Instant::now() + election_every;
+        let mut next_connect = Instant::now();
 
-                            self_id, self_address, peer_address
+        // A dedicated UDP socket for statsd export, bound lazily only when a
+        // statsd endpoint was configured on the command line.
+        let statsd_socket = self.statsd.and_then(|_| {
+            UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).ok()
+        });
+
+        loop {
+            let now = Instant::now();
+
+            if now >= next_announce {
+                if let Err(e) = Arc::clone(&self).announce_presence() {
+                    eprintln!("Neuron {} ({}) error announcing: {}", self.id, self.address, e);
+                }
+                next_announce = now + announce_every;
+            }
+            if now >= next_connect {
+                self.dial_lower_peers(&poller, &mut connections);
+                next_connect = now + connect_every;
+            }
+            if now >= next_election {
+                // Only elect once peers are actually known, and re-elect whenever
+                // we have no coordinator — a single t=0 attempt ran before any
+                // discovery datagram was drained, so every neuron saw an empty
+                // peer set and immediately self-elected.
+                let have_peers = !self.peers.lock().unwrap().addresses().is_empty();
+                let leaderless = self.leader.lock().unwrap().is_none();
+                if have_peers && leaderless {
+                    Arc::clone(&self).start_election();
+                }
+                next_election = now + election_every;
+            }
+            if now >= next_keepalive {
+                for (stream, peer_address) in connections.values_mut() {
+                    let keepalive = Message::from_values(self.id, SignalType::Keepalive, &[self.id as f32]);
+                    if keepalive.send(stream).is_ok() {
+                        self.traffic.lock().unwrap().count_out(
+                            *peer_address,
+                            keepalive.signal_type.to_u8(),
+                            keepalive.frame_len(),
                         );
-                        break;
                     }
-                    Ok(size) => {
-                        println!(
-                            "Neuron {} ({}) received {} bytes from {}",
-                            self_id, self_address, size, peer_address
-                        );
-                        // Here you would use ncp::Message::receive(&mut stream)
-                        match Message::receive(&mut stream) {
-                            Ok(Some(message)) => {
-                                println!(
-                                    "Neuron {} ({}) received NCP message from {}: {:?}",
-
-                                    self_id, self_address, peer_address, message
-                                );
-                                // Process the received message (e.g., for election)
-                            }
-                            Ok(None) => {
-                                println!(
-                                    "Neuron {} ({}) peer {} likely disconnected gracefully (NCP).",
-                                    self_id, self_address, peer_address
-                                );
-                                break;
-                            }
-                            Err(e) => {
-                                eprintln!(
-                                    "Neuron {} ({}) error receiving NCP message from {}: {}",
-                                    self_id, self_address, peer_address, e
-                                );
-                                break;
+                }
+                next_keepalive = now + keepalive_every;
+            }
+            if now >= next_housekeep {
+                for peer_address in self.peers.lock().unwrap().timeout(DEFAULT_PEER_TIMEOUT) {
+                    println!("Neuron {} ({}) Forgot peer {}", self.id, self.address, peer_address);
+                }
+                next_housekeep = now + housekeep_every;
+            }
+            if now >= next_gossip {
+                self.gossip_peers(&mut connections);
+                next_gossip = now + gossip_every;
+            }
+            if now >= next_stats {
+                let mut stats = self.traffic.lock().unwrap();
+                stats.report(self.id);
+                if let (Some(target), Some(socket)) = (self.statsd, statsd_socket.as_ref()) {
+                    for line in stats.statsd_lines(self.id) {
+                        let _ = socket.send_to(line.as_bytes(), target);
+                    }
+                }
+                next_stats = now + stats_every;
+            }
 
-                            }
+            // Sleep only until the nearest timer is due.
+            let now = Instant::now();
+            let nearest = *[next_announce, next_keepalive, next_housekeep, next_stats, next_gossip, next_election, next_connect]
+                .iter()
+                .min()
+                .unwrap();
+            let timeout_ms = nearest.saturating_duration_since(now).as_millis().min(i32::MAX as u128) as i32;
+
+            match poller.wait(timeout_ms) {
+                WaitResult::Timeout => {}
+                WaitResult::Error(e) => {
+                    eprintln!("Neuron {} ({}) poll error: {}", self.id, self.address, e);
+                }
+                WaitResult::Readable(fd) if fd == discovery_fd => {
+                    self.drain_discovery(&discovery, &mut buffer);
+                }
+                WaitResult::Readable(fd) if fd == listener_fd => {
+                    self.accept_connections(&listener, &poller, &mut connections);
+                }
+                WaitResult::Readable(fd) => {
+                    self.service_peer(fd, &poller, &mut connections, &mut read_buffers);
+                }
+            }
+        }
+    }
+
+    /// Drain every pending discovery datagram and merge announced peers.
+    fn drain_discovery(&self, discovery: &UdpSocket, buffer: &mut [u8]) {
+        loop {
+            match discovery.recv_from(buffer) {
+                Ok((size, src_address)) => {
+                    if src_address == self.address {
+                        continue;
+                    }
+                    if let Ok(text) = String::from_utf8(buffer[..size].to_vec()) {
+                        // Announcement is "<address> <public-key-hex>".
+                        self.merge_announcement(&text);
+                    }
+                }
+                Err(_) => break, // WouldBlock: no more datagrams queued
+            }
+        }
+    }
+
+    /// Accept every pending inbound connection, authenticate it, and register
+    /// the stream with the reactor.
+    fn accept_connections(
+        self: &Arc<Self>,
+        listener: &std::net::TcpListener,
+        poller: &Poll,
+        connections: &mut HashMap<RawFd, (TcpStream, SocketAddr)>,
+    ) {
+        loop {
+            match listener.accept() {
+                Ok((mut stream, peer_address)) => {
+                    println!(
+                        "Neuron {} ({}) accepted connection from: {}",
+                        self.id, self.address, peer_address
+                    );
+                    // The handshake is a short synchronous exchange. It also
+                    // tells us the dialer's canonical listen address, which we
+                    // track instead of the ephemeral source port of the accepted
+                    // socket so gossip and coordinator-loss detection see real,
+                    // reachable addresses.
+                    let listen_address = match crypto::handshake(&mut stream, &self.identity, None, self.address) {
+                        Ok((_, address)) => address,
+                        Err(e) => {
+                            eprintln!(
+                                "Neuron {} ({}) handshake with {} failed: {}",
+                                self.id, self.address, peer_address, e
+                            );
+                            continue;
                         }
+                    };
+                    self.peers.lock().unwrap().seen(listen_address);
+                    self.register_peer(stream, listen_address, poller, connections);
+                }
+                Err(_) => break, // WouldBlock: no more pending connections
+            }
+        }
+    }
+
+    /// Connect to known peers with a lower address that we are not already
+    /// connected to, authenticate, and register the resulting streams.
+    fn dial_lower_peers(
+        self: &Arc<Self>,
+        poller: &Poll,
+        connections: &mut HashMap<RawFd, (TcpStream, SocketAddr)>,
+    ) {
+        let connected: std::collections::HashSet<SocketAddr> =
+            connections.values().map(|(_, addr)| *addr).collect();
+        let candidates: Vec<SocketAddr> = self
+            .peers
+            .lock()
+            .unwrap()
+            .addresses()
+            .into_iter()
+            .filter(|addr| *addr < self.address && !connected.contains(addr))
+            .collect();
+
+        for peer_address in candidates {
+            match connect_timeout(&peer_address, CONNECT_TIMEOUT) {
+                Ok(mut stream) => {
+                    let expected = self.peers.lock().unwrap().advertised_key(&peer_address);
+                    if let Err(e) = crypto::handshake(&mut stream, &self.identity, expected, self.address) {
+                        eprintln!(
+                            "Neuron {} ({}) handshake with {} failed: {}",
+                            self.id, self.address, peer_address, e
+                        );
+                        continue;
                     }
-                    Err(e) => {
+                    let hello = Message::from_values(self.id, SignalType::Data, &[self.id as f32]);
+                    if let Err(e) = hello.send(&mut stream) {
                         eprintln!(
-                            "Neuron {} ({}) error reading from {}: {}",
-                            self_id, self_address, peer_address, e
+                            "Neuron {} ({}) error sending initial message to {}: {}",
+                            self.id, self.address, peer_address, e
+                        );
+                    } else {
+                        self.traffic.lock().unwrap().count_out(
+                            peer_address,
+                            hello.signal_type.to_u8(),
+                            hello.frame_len(),
                         );
+                    }
+                    self.register_peer(stream, peer_address, poller, connections);
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Neuron {} ({}) error connecting to {}: {}",
+                        self.id, self.address, peer_address, e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Exchange peer lists with a random `fanout`-sized subset of live
+    /// connections. Each selected peer receives a `PeerExchange` carrying every
+    /// address we currently know, so membership spreads epidemically. Only the
+    /// gossip traffic is limited to `fanout` peers per round: the set of
+    /// connections is not, since `dial_lower_peers` dials every known peer with
+    /// a lower address and every inbound connection is accepted.
+    fn gossip_peers(&self, connections: &mut HashMap<RawFd, (TcpStream, SocketAddr)>) {
+        if connections.is_empty() {
+            return;
+        }
+        let known = self.peers.lock().unwrap().addresses();
+        if known.is_empty() {
+            return;
+        }
+        let payload = encode_peer_batch(&known);
+
+        // Partial Fisher-Yates over the connection fds, same as PeerList::subset.
+        let mut fds: Vec<RawFd> = connections.keys().copied().collect();
+        let count = self.fanout.min(fds.len());
+        let mut rng = OsRng;
+        for i in 0..count {
+            let j = i + (rng.next_u32() as usize) % (fds.len() - i);
+            fds.swap(i, j);
+        }
+
+        for fd in &fds[..count] {
+            if let Some((stream, peer_address)) = connections.get_mut(fd) {
+                let message = Message::new(self.id, SignalType::PeerExchange, payload.clone());
+                if message.send(stream).is_ok() {
+                    self.traffic.lock().unwrap().count_out(
+                        *peer_address,
+                        message.signal_type.to_u8(),
+                        message.frame_len(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Merge a gossiped batch of peer addresses into our own view, de-duplicating
+    /// against peers we already know and ignoring our own address.
+    fn merge_peer_batch(&self, payload: &[u8]) {
+        let Ok(text) = String::from_utf8(payload.to_vec()) else {
+            return;
+        };
+        let mut peers = self.peers.lock().unwrap();
+        for token in text.split_whitespace() {
+            if let Ok(address) = token.parse::<SocketAddr>() {
+                if address == self.address {
+                    continue;
+                }
+                if !peers.contains(&address) {
+                    println!(
+                        "Neuron {} ({}) learned peer {} via gossip",
+                        self.id, self.address, address
+                    );
+                }
+                peers.seen(address);
+            }
+        }
+    }
+
+    /// Set a freshly handshaken peer stream non-blocking and register it.
+    fn register_peer(
+        &self,
+        stream: TcpStream,
+        peer_address: SocketAddr,
+        poller: &Poll,
+        connections: &mut HashMap<RawFd, (TcpStream, SocketAddr)>,
+    ) {
+        if stream.set_nonblocking(true).is_err() {
+            return;
+        }
+        let fd = stream.as_raw_fd();
+        if poller.register(fd).is_ok() {
+            connections.insert(fd, (stream, peer_address));
+        }
+    }
+
+    /// Handle readability on a peer stream: drain whatever bytes are available
+    /// without blocking, dispatch every complete frame now buffered, and tear
+    /// the connection down on close/error.
+    ///
+    /// The stream stays non-blocking throughout. A peer that advertises a
+    /// payload length and then stalls mid-frame leaves its partial bytes in this
+    /// connection's buffer and never holds up the reactor, so one slow or
+    /// malicious sender can't freeze every other peer, the timers, or discovery.
+    fn service_peer(
+        self: &Arc<Self>,
+        fd: RawFd,
+        poller: &Poll,
+        connections: &mut HashMap<RawFd, (TcpStream, SocketAddr)>,
+        read_buffers: &mut HashMap<RawFd, Vec<u8>>,
+    ) {
+        let Some((_, peer_address)) = connections.get(&fd) else {
+            return;
+        };
+        let peer_address = *peer_address;
+
+        // Drain every byte currently readable into this fd's frame buffer.
+        let mut closed = false;
+        {
+            let (stream, _) = connections.get_mut(&fd).unwrap();
+            let buffer = read_buffers.entry(fd).or_default();
+            let mut chunk = [0u8; 4096];
+            loop {
+                match stream.read(&mut chunk) {
+                    Ok(0) => {
+                        closed = true;
+                        break;
+                    }
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        closed = true;
                         break;
                     }
                 }
             }
-        });
+        }
+
+        // Dispatch each complete frame; leave any trailing partial frame buffered.
+        loop {
+            let decoded = Message::parse(read_buffers.get(&fd).map(Vec::as_slice).unwrap_or(&[]));
+            match decoded {
+                Decoded::Incomplete => break,
+                Decoded::Invalid(consumed) => {
+                    read_buffers.get_mut(&fd).unwrap().drain(..consumed);
+                }
+                Decoded::Message(message, consumed) => {
+                    read_buffers.get_mut(&fd).unwrap().drain(..consumed);
+                    println!(
+                        "Neuron {} ({}) received NCP message from {}: {:?}",
+                        self.id, self.address, peer_address, message
+                    );
+                    self.peers.lock().unwrap().seen(peer_address); // Any message proves liveness
+                    self.traffic.lock().unwrap().count_in(
+                        peer_address,
+                        message.signal_type.to_u8(),
+                        message.frame_len(),
+                    );
+                    let (stream, _) = connections.get_mut(&fd).unwrap();
+                    Self::handle_signal(Arc::clone(self), &message, stream, peer_address);
+                }
+            }
+        }
+
+        if closed {
+            println!(
+                "Neuron {} ({}) peer {} disconnected.",
+                self.id, self.address, peer_address
+            );
+            let _ = poller.deregister(fd);
+            connections.remove(&fd);
+            read_buffers.remove(&fd);
+            // If the peer that vanished was our coordinator, re-elect — but only
+            // once no live connection to that leader id remains. Victory arrives
+            // over a short-lived connection that closes the instant the send
+            // returns; without this check that close would be read as the
+            // coordinator going away and trigger an election storm, so the mesh
+            // would never settle on a leader.
+            let leader_id = id_from_addr(&peer_address);
+            let mut leader_guard = self.leader.lock().unwrap();
+            if *leader_guard == Some(leader_id)
+                && !connections.values().any(|(_, addr)| id_from_addr(addr) == leader_id)
+            {
+                println!(
+                    "Neuron {} ({}) lost its coordinator {}, re-electing.",
+                    self.id, self.address, peer_address
+                );
+                *leader_guard = None;
+                drop(leader_guard);
+                Arc::clone(self).start_election();
+            }
+        }
     }
 
-    fn listen_for_connections(self: Arc<Self>) {
-        let listener = listen(&self.address.to_string()).expect("Failed to start listener");
+    /// React to a single received signal according to the Bully protocol.
+    fn handle_signal(self: Arc<Self>, message: &Message, stream: &mut TcpStream, peer_address: SocketAddr) {
         let self_id = self.id;
         let self_address = self.address;
-        let peers = Arc::clone(&self.peers);
-
-        thread::spawn(move || {
-            println!("Neuron {} ({}) listening for incoming connections.", self_id, self_address);
-            for stream in listener.incoming() {
-                match stream {
-                    Ok(stream) => {
-                        let peer_address = stream.peer_addr().unwrap();
-                        println!(
-                            "Neuron {} ({}) accepted connection from: {}",
-                            self_id, self_address, peer_address
-                        );
-                        let mut peers_guard = peers.lock().unwrap();
-                        if !peers_guard.contains(&peer_address) {
-                            peers_guard.push(peer_address);
-                        }
-                        drop(peers_guard);
-                        Self::handle_peer_communication(Arc::clone(&self), stream, peer_address);
-                    }
-                    Err(e) => {
+        match message.signal_type {
+            SignalType::ElectionRequest => {
+                // A lower-id peer is challenging: nominate ourselves and start
+                // our own election against even-higher peers.
+                if message.sender_id < self_id {
+                    let nomination = Message::from_values(self_id, SignalType::Nomination, &[self_id as f32]);
+                    if let Err(e) = nomination.send(stream) {
                         eprintln!(
-                            "Neuron {} ({}) error accepting connection: {}",
+                            "Neuron {} ({}) error sending Nomination: {}",
                             self_id, self_address, e
                         );
+                    } else {
+                        self.traffic.lock().unwrap().count_out(
+                            peer_address,
+                            nomination.signal_type.to_u8(),
+                            nomination.frame_len(),
+                        );
                     }
+                    Arc::clone(&self).start_election();
                 }
             }
-            println!("Neuron {} ({}) listener stopped.", self_id, self_address);
-        });
+            SignalType::PeerExchange => {
+                // A gossip round from a peer: fold its address batch into ours.
+                self.merge_peer_batch(&message.payload);
+            }
+            SignalType::Victory => {
+                // Record the announced coordinator.
+                *self.leader.lock().unwrap() = Some(message.sender_id);
+                println!(
+                    "Neuron {} ({}) acknowledges neuron {} as coordinator.",
+                    self_id, self_address, message.sender_id
+                );
+            }
+            _ => {}
+        }
     }
+
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <number_of_neurons>", args[0]);
+    if args.len() < 2 {
+        eprintln!(
+            "Usage: {} <number_of_neurons> [--statsd <host:port>] [--discovery broadcast|beacon:<path-or-url>] [--fanout <n>]",
+            args[0]
+        );
         return;
     }
+
+    // Optional `--statsd <host:port>` turns on UDP statsd export of the traffic
+    // counters; without it the reporter only logs the table. `--discovery`
+    // selects the peer-discovery backend, defaulting to LAN UDP broadcast.
+    let mut statsd: Option<SocketAddr> = None;
+    let mut discovery = Discovery::Broadcast;
+    let mut fanout = DEFAULT_FANOUT;
+    let mut rest = args[2..].iter();
+    while let Some(flag) = rest.next() {
+        match flag.as_str() {
+            "--statsd" => match rest.next().and_then(|v| v.parse::<SocketAddr>().ok()) {
+                Some(address) => statsd = Some(address),
+                None => {
+                    eprintln!("--statsd requires a <host:port> argument");
+                    return;
+                }
+            },
+            "--discovery" => match rest.next().map(|v| Discovery::parse(v)) {
+                Some(Ok(backend)) => discovery = backend,
+                Some(Err(e)) => {
+                    eprintln!("Invalid --discovery argument: {}", e);
+                    return;
+                }
+                None => {
+                    eprintln!("--discovery requires an argument");
+                    return;
+                }
+            },
+            "--fanout" => match rest.next().and_then(|v| v.parse::<usize>().ok()) {
+                Some(n) if n > 0 => fanout = n,
+                _ => {
+                    eprintln!("--fanout requires a positive integer");
+                    return;
+                }
+            },
+            other => {
+                eprintln!("Unknown argument: {}", other);
+                return;
+            }
+        }
+    }
+
     let num_neurons_str = &args[1];
     let num_neurons = match num_neurons_str.parse::<u16>() {
         Ok(n) if n > 0 && n <= 65535 - NEURON_PORT_START => n,
@@ -272,25 +873,11 @@ fn main() {
     let mut neurons = Vec::new();
     let mut neuron_handles: Vec<std::thread::JoinHandle<()>> = Vec::new();
     for i in 0..num_neurons {
-        let neuron = Arc::new(Neuron::new(i));
-        Arc::clone(&neuron).listen_for_announcements(); // Clone before calling
-        Arc::clone(&neuron).listen_for_connections(); // Clone before calling
-        Arc::clone(&neuron).connect_to_peers();     // Clone before calling
+        let neuron = Arc::new(Neuron::new(i, statsd, discovery.clone(), fanout));
         neurons.push(Arc::clone(&neuron));
 
-        // Announce presence periodically
-        let neuron_clone = Arc::clone(&neuron);
-        let handle = thread::spawn(move || {
-            loop {
-                if let Err(e) = Arc::clone(&neuron_clone).announce_presence() {
-                    eprintln!(
-                        "Neuron {} ({}) error announcing: {} | peers {:?}",
-                        neuron_clone.id, neuron_clone.address, e, neuron_clone.peers
-                    );
-                }
-                thread::sleep(Duration::from_millis(ANNOUNCE_INTERVAL_MS));
-            }
-        });
+        // One reactor thread drives all of this neuron's I/O and timers.
+        let handle = thread::spawn(move || neuron.run_reactor());
         neuron_handles.push(handle);
     }
 
@@ -300,3 +887,48 @@ fn main() {
         thread::sleep(Duration::from_secs(60));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+    }
+
+    #[test]
+    fn timeout_forgets_only_stale_peers() {
+        let mut peers = PeerList::new();
+        peers.seen(addr(NEURON_PORT_START));
+        thread::sleep(Duration::from_millis(10));
+        peers.seen(addr(NEURON_PORT_START + 1)); // refreshed just now
+
+        let forgotten = peers.timeout(Duration::from_millis(5));
+        assert_eq!(forgotten, vec![addr(NEURON_PORT_START)]);
+        assert!(!peers.contains(&addr(NEURON_PORT_START)));
+        assert!(peers.contains(&addr(NEURON_PORT_START + 1)));
+    }
+
+    #[test]
+    fn subset_samples_at_most_count_distinct_known_peers() {
+        let mut peers = PeerList::new();
+        for offset in 0..10 {
+            peers.seen(addr(NEURON_PORT_START + offset));
+        }
+        let known: std::collections::HashSet<SocketAddr> = peers.addresses().into_iter().collect();
+
+        let sample = peers.subset(3);
+        assert_eq!(sample.len(), 3);
+        let unique: std::collections::HashSet<SocketAddr> = sample.iter().copied().collect();
+        assert_eq!(unique.len(), 3); // no duplicates
+        assert!(sample.iter().all(|address| known.contains(address)));
+    }
+
+    #[test]
+    fn subset_returns_all_when_fewer_than_count() {
+        let mut peers = PeerList::new();
+        peers.seen(addr(NEURON_PORT_START));
+        peers.seen(addr(NEURON_PORT_START + 1));
+        assert_eq!(peers.subset(5).len(), 2);
+    }
+}
@@ -2,8 +2,9 @@
 // Lightweigh socket programming to facilitate communication between Neurons
 
 
-use std::net::{TcpStream, TcpListener};
+use std::net::{SocketAddr, TcpStream, TcpListener};
 use std::io::{Read, Write, Result};
+use std::time::Duration;
 
 /// Represents the different types of signals in the Neuron Communication Protocol (NCP)
 #[derive(Debug)]
@@ -13,6 +14,8 @@ pub enum SignalType {
     Nomination = 2,
     Vote = 3,
     Victory = 4,
+    Keepalive = 5,
+    PeerExchange = 6,
 }
 
 impl SignalType {
@@ -23,6 +26,8 @@ impl SignalType {
             2 => Some(SignalType::Nomination),
             3 => Some(SignalType::Vote),
             4 => Some(SignalType::Victory),
+            5 => Some(SignalType::Keepalive),
+            6 => Some(SignalType::PeerExchange),
             _ => None,
         }
 
@@ -35,50 +40,139 @@ impl SignalType {
             SignalType::Nomination => 2,
             SignalType::Vote => 3,
             SignalType::Victory => 4,
+            SignalType::Keepalive => 5,
+            SignalType::PeerExchange => 6,
 
         }
     }
 }
 
-/// Represents a message in the Neuron Communication Protocol (NCP)
+/// Represents a message in the Neuron Communication Protocol (NCP).
+///
+/// The wire format is a fixed 5-byte header followed by a variable body:
+/// `sender_id` (2 bytes) + `signal_type` (1 byte) + `length` (2 bytes,
+/// big-endian) + `length` bytes of `payload`. Election signals carry a tiny
+/// payload, while `SignalType::Data` can carry a full activation/weight vector.
 #[derive(Debug)]
 pub struct Message {
     pub sender_id: u16,     // 2 bytes: Neuron ID (0-65535)
     pub signal_type: SignalType, // 1 byte: Type of signal
-    pub value: f32,         // 4 bytes: Scalar value (can be repurposed for election data)
+    pub payload: Vec<u8>,   // length-prefixed body
+}
+
+/// Length of the fixed NCP header: sender_id + signal_type + payload length.
+const HEADER_LEN: usize = 5;
+
+/// Result of attempting to decode a single NCP frame from the front of a read
+/// buffer. Lets a non-blocking reader accumulate bytes across several reads and
+/// pull out whole frames without ever blocking on a half-arrived payload.
+pub enum Decoded {
+    /// A complete, well-formed message and the number of bytes it consumed.
+    Message(Message, usize),
+    /// A complete frame carrying an unknown signal type; skip `usize` bytes.
+    Invalid(usize),
+    /// Not enough bytes buffered to decode a frame yet.
+    Incomplete,
 }
 
 
 impl Message {
-    /// Create a new NCP message
-    pub fn new(sender_id: u16, signal_type: SignalType, value: f32) -> Self {
+    /// Create a new NCP message with a raw byte payload
+    pub fn new(sender_id: u16, signal_type: SignalType, payload: Vec<u8>) -> Self {
         Message {
             sender_id,
             signal_type,
-            value,
+            payload,
         }
 
     }
 
+    /// Create a new NCP message whose payload is an activation/weight vector,
+    /// serialized as consecutive big-endian `f32` values.
+    pub fn from_values(sender_id: u16, signal_type: SignalType, values: &[f32]) -> Self {
+        let mut payload = Vec::with_capacity(values.len() * 4);
+        for value in values {
+            payload.extend_from_slice(&value.to_be_bytes());
+        }
+        Message {
+            sender_id,
+            signal_type,
+            payload,
+        }
+    }
+
     /// Create a new NCP message with a SignalType from a u8
-    pub fn new_with_u8_signal(sender_id: u16, signal_type: u8, value: f32) -> Option<Self> {
+    pub fn new_with_u8_signal(sender_id: u16, signal_type: u8, payload: Vec<u8>) -> Option<Self> {
 
         SignalType::from_u8(signal_type).map(|st| Message {
             sender_id,
             signal_type: st,
-            value,
+            payload,
         })
     }
 
+    /// Interpret the payload as a vector of big-endian `f32` values. Trailing
+    /// bytes that don't form a whole `f32` are ignored.
+    pub fn values(&self) -> Vec<f32> {
+        self.payload
+            .chunks_exact(4)
+            .map(|chunk| f32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+
+    /// Total size of this message on the wire: fixed header plus payload. Used
+    /// by the traffic accounting to charge a peer the full frame length.
+    pub fn frame_len(&self) -> usize {
+        HEADER_LEN + self.payload.len()
+    }
+
+    /// Decode one frame from the front of `buf` without consuming the buffer.
+    /// Returns [`Decoded::Incomplete`] when `buf` does not yet hold a full frame,
+    /// so a non-blocking reader can buffer partial frames per connection instead
+    /// of blocking the reactor waiting for the rest of a payload.
+    pub fn parse(buf: &[u8]) -> Decoded {
+        if buf.len() < HEADER_LEN {
+            return Decoded::Incomplete;
+        }
+        let sender_id = u16::from_be_bytes([buf[0], buf[1]]);
+        let signal_type_u8 = buf[2];
+        let length = u16::from_be_bytes([buf[3], buf[4]]) as usize;
+        let total = HEADER_LEN + length;
+        if buf.len() < total {
+            return Decoded::Incomplete;
+        }
+        let payload = buf[HEADER_LEN..total].to_vec();
+        match SignalType::from_u8(signal_type_u8) {
+            Some(signal_type) => Decoded::Message(
+                Message {
+                    sender_id,
+                    signal_type,
+                    payload,
+                },
+                total,
+            ),
+            None => {
+                eprintln!("Warning: Received message with invalid signal type: {}", signal_type_u8);
+                Decoded::Invalid(total)
+            }
+        }
+    }
+
+    /// Serialize the message to its on-the-wire bytes: the fixed header followed
+    /// by the length-prefixed payload.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let length = self.payload.len() as u16;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(&self.sender_id.to_be_bytes()); // Big-endian for network
+        bytes.push(self.signal_type.to_u8());
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
     /// Serialize and send the message over a TCP stream
     pub fn send(&self, stream: &mut TcpStream) -> Result<()> {
-        let bytes = [
-            self.sender_id.to_be_bytes().as_slice(), // Big-endian for network
-            &[self.signal_type.to_u8()],
-            self.value.to_be_bytes().as_slice(),
-        ].concat();
-
-        stream.write_all(&bytes)?;
+        stream.write_all(&self.to_bytes())?;
         stream.flush()?;
 
         Ok(())
@@ -88,20 +182,23 @@ impl Message {
     /// Receive a message from a TCP stream
     pub fn receive(stream: &mut TcpStream) -> Result<Option<Self>> {
 
-        let mut buffer = [0u8; 7]; // 2 + 1 + 4 = 7 bytes
-        match stream.peek(&mut buffer) {
+        let mut header = [0u8; HEADER_LEN];
+        match stream.peek(&mut header) {
             Ok(0) => return Ok(None), // Connection closed
             Ok(_) => {
-                stream.read_exact(&mut buffer)?;
-                let sender_id = u16::from_be_bytes([buffer[0], buffer[1]]);
-                let signal_type_u8 = buffer[2];
-                let value = f32::from_be_bytes([buffer[3], buffer[4], buffer[5], buffer[6]]);
+                stream.read_exact(&mut header)?;
+                let sender_id = u16::from_be_bytes([header[0], header[1]]);
+                let signal_type_u8 = header[2];
+                let length = u16::from_be_bytes([header[3], header[4]]) as usize;
+
+                let mut payload = vec![0u8; length];
+                stream.read_exact(&mut payload)?;
 
                 if let Some(signal_type) = SignalType::from_u8(signal_type_u8) {
                     Ok(Some(Message {
                         sender_id,
                         signal_type,
-                        value,
+                        payload,
                     }))
                 } else {
                     // Handle invalid signal type (optional: could return an error instead)
@@ -126,3 +223,77 @@ pub fn connect(address: &str) -> Result<TcpStream> {
     let stream = TcpStream::connect(address)?;
     Ok(stream)
 }
+
+/// Connect to a remote NCP endpoint, giving up after `timeout`. Used on the
+/// reactor thread so an unreachable peer can't wedge all of a neuron's I/O in a
+/// blocking `connect`.
+pub fn connect_timeout(address: &SocketAddr, timeout: Duration) -> Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(address, timeout)?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_round_trips_a_frame() {
+        let message = Message::from_values(42, SignalType::Data, &[1.0, -2.5, 3.0]);
+        let bytes = message.to_bytes();
+        match Message::parse(&bytes) {
+            Decoded::Message(decoded, consumed) => {
+                assert_eq!(consumed, bytes.len());
+                assert_eq!(decoded.sender_id, 42);
+                assert_eq!(decoded.signal_type.to_u8(), SignalType::Data.to_u8());
+                assert_eq!(decoded.values(), vec![1.0, -2.5, 3.0]);
+            }
+            _ => panic!("expected a decoded message"),
+        }
+    }
+
+    #[test]
+    fn parse_reports_a_partial_frame_as_incomplete() {
+        let bytes = Message::from_values(7, SignalType::Keepalive, &[7.0]).to_bytes();
+        // A header advertising more payload than is present must not decode.
+        for len in 0..bytes.len() {
+            assert!(matches!(Message::parse(&bytes[..len]), Decoded::Incomplete));
+        }
+    }
+
+    #[test]
+    fn parse_consumes_only_one_frame_from_a_batch() {
+        let first = Message::from_values(1, SignalType::Victory, &[1.0]).to_bytes();
+        let second = Message::from_values(2, SignalType::Keepalive, &[2.0]).to_bytes();
+        let mut buffer = first.clone();
+        buffer.extend_from_slice(&second);
+        match Message::parse(&buffer) {
+            Decoded::Message(_, consumed) => assert_eq!(consumed, first.len()),
+            _ => panic!("expected the first frame"),
+        }
+    }
+
+    #[test]
+    fn send_and_receive_round_trip_over_a_loopback_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let sender = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            Message::from_values(9, SignalType::PeerExchange, &[4.0, 5.0]).send(&mut stream).unwrap();
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let received = Message::receive(&mut stream).unwrap().expect("a message");
+        assert_eq!(received.sender_id, 9);
+        assert_eq!(received.values(), vec![4.0, 5.0]);
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn parse_skips_a_frame_with_an_unknown_signal_type() {
+        let mut bytes = Message::new(3, SignalType::Data, vec![0xAA, 0xBB]).to_bytes();
+        bytes[2] = 0xFF; // corrupt the signal-type byte
+        match Message::parse(&bytes) {
+            Decoded::Invalid(consumed) => assert_eq!(consumed, bytes.len()),
+            _ => panic!("expected an invalid frame"),
+        }
+    }
+}
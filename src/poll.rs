@@ -0,0 +1,72 @@
+// A thin `epoll` wrapper driving all neuron I/O from a single reactor thread.
+//
+// Modeled on VpnCloud's `poll::WaitImpl`/`WaitResult`: every socket the neuron
+// cares about — the discovery UDP socket, the TCP listener, and each accepted
+// or connected peer stream — is registered with one `epoll` instance and set
+// non-blocking. The reactor blocks in [`Poll::wait`] until a file descriptor is
+// readable or the timeout (the nearest pending timer) elapses, then dispatches.
+
+use std::io;
+use std::os::unix::io::RawFd;
+
+/// Outcome of a single [`Poll::wait`] call.
+pub enum WaitResult {
+    /// The timeout elapsed before any descriptor became readable.
+    Timeout,
+    /// The descriptor is readable.
+    Readable(RawFd),
+    /// `epoll_wait` failed.
+    Error(io::Error),
+}
+
+/// An `epoll` instance that readable descriptors can be registered with.
+pub struct Poll {
+    epoll_fd: RawFd,
+    event: libc::epoll_event,
+}
+
+impl Poll {
+    pub fn new() -> io::Result<Self> {
+        let epoll_fd = unsafe { libc::epoll_create1(0) };
+        if epoll_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Poll { epoll_fd, event: libc::epoll_event { events: 0, u64: 0 } })
+    }
+
+    /// Start watching `fd` for readability. The descriptor doubles as the event
+    /// token, so [`WaitResult::Readable`] hands it straight back.
+    pub fn register(&self, fd: RawFd) -> io::Result<()> {
+        let mut event = libc::epoll_event { events: libc::EPOLLIN as u32, u64: fd as u64 };
+        let rc = unsafe { libc::epoll_ctl(self.epoll_fd, libc::EPOLL_CTL_ADD, fd, &mut event) };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Stop watching `fd` (e.g. when a peer disconnects).
+    pub fn deregister(&self, fd: RawFd) -> io::Result<()> {
+        let rc = unsafe { libc::epoll_ctl(self.epoll_fd, libc::EPOLL_CTL_DEL, fd, std::ptr::null_mut()) };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Block until one descriptor is readable or `timeout_ms` elapses.
+    pub fn wait(&mut self, timeout_ms: i32) -> WaitResult {
+        let count = unsafe { libc::epoll_wait(self.epoll_fd, &mut self.event, 1, timeout_ms) };
+        match count {
+            0 => WaitResult::Timeout,
+            n if n > 0 => WaitResult::Readable(self.event.u64 as RawFd),
+            _ => WaitResult::Error(io::Error::last_os_error()),
+        }
+    }
+}
+
+impl Drop for Poll {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.epoll_fd) };
+    }
+}
@@ -0,0 +1,129 @@
+// Per-peer and per-signal traffic accounting for the neuron mesh.
+//
+// Modeled on VpnCloud's `TrafficStats`: every frame that leaves via
+// `Message::send` or arrives on a peer stream bumps a byte and a message
+// counter, bucketed both by peer and by `SignalType`. A reporter prints the
+// table every `STATS_INTERVAL_MS` and, when a statsd endpoint is configured,
+// emits the counters as statsd counter lines.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// Byte and frame counters for a single bucket (a peer or a signal type).
+#[derive(Default, Debug, Clone)]
+pub struct Counters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_msgs: u64,
+    pub tx_msgs: u64,
+}
+
+/// Aggregate traffic counters for one neuron.
+#[derive(Default, Debug)]
+pub struct TrafficStats {
+    peers: HashMap<SocketAddr, Counters>,
+    signals: HashMap<u8, Counters>,
+    /// Per-peer totals as of the last statsd export, so we can emit per-interval
+    /// deltas rather than re-sending the running total every interval.
+    last_export: HashMap<SocketAddr, Counters>,
+}
+
+impl TrafficStats {
+    pub fn new() -> Self {
+        TrafficStats::default()
+    }
+
+    /// Record a frame of `bytes` bytes sent to `peer` carrying `signal`.
+    pub fn count_out(&mut self, peer: SocketAddr, signal: u8, bytes: usize) {
+        let peer_entry = self.peers.entry(peer).or_default();
+        peer_entry.tx_bytes += bytes as u64;
+        peer_entry.tx_msgs += 1;
+        let signal_entry = self.signals.entry(signal).or_default();
+        signal_entry.tx_bytes += bytes as u64;
+        signal_entry.tx_msgs += 1;
+    }
+
+    /// Record a frame of `bytes` bytes received from `peer` carrying `signal`.
+    pub fn count_in(&mut self, peer: SocketAddr, signal: u8, bytes: usize) {
+        let peer_entry = self.peers.entry(peer).or_default();
+        peer_entry.rx_bytes += bytes as u64;
+        peer_entry.rx_msgs += 1;
+        let signal_entry = self.signals.entry(signal).or_default();
+        signal_entry.rx_bytes += bytes as u64;
+        signal_entry.rx_msgs += 1;
+    }
+
+    /// Log the current counters as a human-readable table.
+    pub fn report(&self, neuron_id: u16) {
+        println!(
+            "Neuron {} traffic | {:<24} {:>10} {:>10} {:>8} {:>8}",
+            neuron_id, "peer", "rx_bytes", "tx_bytes", "rx_msgs", "tx_msgs"
+        );
+        for (peer, counters) in &self.peers {
+            println!(
+                "Neuron {} traffic | {:<24} {:>10} {:>10} {:>8} {:>8}",
+                neuron_id,
+                peer.to_string(),
+                counters.rx_bytes,
+                counters.tx_bytes,
+                counters.rx_msgs,
+                counters.tx_msgs
+            );
+        }
+    }
+
+    /// Render the traffic since the previous export as statsd counter lines,
+    /// e.g. `neuron.<id>.peer.<addr>.rx_bytes:<delta>|c`.
+    ///
+    /// A `|c` line tells statsd to *increment* by the value, so we must emit the
+    /// per-interval delta; sending the running total each interval would make
+    /// the server sum the totals and overcount every counter.
+    pub fn statsd_lines(&mut self, neuron_id: u16) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (peer, counters) in &self.peers {
+            let last = self.last_export.get(peer).cloned().unwrap_or_default();
+            let addr = sanitize(&peer.to_string());
+            let prefix = format!("neuron.{}.peer.{}", neuron_id, addr);
+            lines.push(format!("{}.rx_bytes:{}|c", prefix, counters.rx_bytes - last.rx_bytes));
+            lines.push(format!("{}.tx_bytes:{}|c", prefix, counters.tx_bytes - last.tx_bytes));
+            lines.push(format!("{}.rx_msgs:{}|c", prefix, counters.rx_msgs - last.rx_msgs));
+            lines.push(format!("{}.tx_msgs:{}|c", prefix, counters.tx_msgs - last.tx_msgs));
+        }
+        self.last_export = self.peers.clone();
+        lines
+    }
+}
+
+/// Replace statsd-unfriendly characters in an address label.
+fn sanitize(label: &str) -> String {
+    label.replace([':', '.'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> SocketAddr {
+        "127.0.0.1:5003".parse().unwrap()
+    }
+
+    #[test]
+    fn statsd_lines_emit_per_interval_deltas() {
+        let mut stats = TrafficStats::new();
+        stats.count_out(peer(), 0, 10);
+
+        // First export reports the full counter as the delta from zero.
+        let first = stats.statsd_lines(1);
+        assert!(first.contains(&"neuron.1.peer.127_0_0_1_5003.tx_bytes:10|c".to_string()));
+
+        // More traffic, then a second export reports only the new bytes, not the
+        // running total.
+        stats.count_out(peer(), 0, 5);
+        let second = stats.statsd_lines(1);
+        assert!(second.contains(&"neuron.1.peer.127_0_0_1_5003.tx_bytes:5|c".to_string()));
+
+        // With no further traffic the next export is a zero delta.
+        let third = stats.statsd_lines(1);
+        assert!(third.contains(&"neuron.1.peer.127_0_0_1_5003.tx_bytes:0|c".to_string()));
+    }
+}